@@ -7,6 +7,7 @@ use std::{
 
 use anyhow::Result;
 use helix_core::NATIVE_LINE_ENDING;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     clipboard::{get_clipboard_provider, ClipboardProvider, ClipboardType},
@@ -19,6 +20,37 @@ use crate::{
 /// make space for new yanks.
 const MAX_REGISTER_HISTORY_LEN: usize = 100;
 
+/// The register that unqualified yanks and deletes are written to, and whose
+/// yank history the numbered registers (`"0`..`"9`) index into.
+const DEFAULT_REGISTER_NAME: char = '"';
+
+/// The shape of the selection that produced a yank.
+///
+/// Pasting consults this to decide how the values should be inserted: a
+/// linewise yank is always pasted as whole lines above or below the cursor's
+/// line, regardless of the cursor's column, while a charwise yank is pasted
+/// at the cursor position.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RegisterShape {
+    /// An arbitrary, potentially sub-line selection.
+    #[default]
+    Charwise,
+    /// One or more whole lines.
+    Linewise,
+    /// A rectangular block selection.
+    Blockwise,
+}
+
+/// The shape of a yank formed by appending `other` onto `existing`, matching
+/// Vim's rule that an append is linewise if either side is.
+fn merge_shapes(existing: RegisterShape, other: RegisterShape) -> RegisterShape {
+    if existing == RegisterShape::Linewise || other == RegisterShape::Linewise {
+        RegisterShape::Linewise
+    } else {
+        existing
+    }
+}
+
 #[derive(Debug, Default)]
 struct Register {
     /// The values held by the register.
@@ -35,6 +67,8 @@ struct Register {
     values: VecDeque<String>,
     /// The length of each yank into the register.
     lengths: VecDeque<NonZeroUsize>,
+    /// The shape of each yank into the register, parallel to `lengths`.
+    shapes: VecDeque<RegisterShape>,
 }
 
 impl Register {
@@ -44,22 +78,52 @@ impl Register {
 
     fn values(&self) -> RegisterValues<'_> {
         let length = self.lengths.back().map(|len| len.get()).unwrap_or_default();
-        RegisterValues::new(
+        let shape = self.shapes.back().copied().unwrap_or_default();
+        RegisterValues::with_shape(
             self.values
                 .iter()
                 .rev()
                 .take(length)
                 .rev()
                 .map(|s| Cow::Borrowed(s.as_str())),
+            shape,
         )
     }
 
-    fn write<I: IntoIterator<Item = String>>(&mut self, values: I) {
+    /// The `n`-th most recent yank into this register, where `0` is the
+    /// latest (equivalent to `values`). Returns `None` if there are fewer
+    /// than `n + 1` yanks in the history.
+    fn values_nth(&self, n: usize) -> Option<RegisterValues<'_>> {
+        if n >= self.lengths.len() {
+            return None;
+        }
+
+        // Lengths and shapes are stored oldest to newest, so the n-th most
+        // recent entry is `n` from the back.
+        let index = self.lengths.len() - 1 - n;
+        let skip_from_end: usize = self.lengths.iter().rev().take(n).map(|len| len.get()).sum();
+        let length = self.lengths[index].get();
+        let shape = self.shapes[index];
+        let end = self.values.len() - skip_from_end;
+        let start = end - length;
+
+        Some(RegisterValues::with_shape(
+            self.values
+                .iter()
+                .skip(start)
+                .take(end - start)
+                .map(|s| Cow::Borrowed(s.as_str())),
+            shape,
+        ))
+    }
+
+    fn write<I: IntoIterator<Item = String>>(&mut self, values: I, shape: RegisterShape) {
         // If the register is full, discard the oldest yank in history.
         if self.lengths.len() > MAX_REGISTER_HISTORY_LEN {
             // Greater than max length implies non-empty.
             let oldest_len = self.lengths.pop_front().unwrap();
             self.values.drain(..oldest_len.get());
+            self.shapes.pop_front();
         }
 
         let pre_yank_len = self.values.len();
@@ -67,14 +131,67 @@ impl Register {
         let yank_len = NonZeroUsize::new(self.values.len() - pre_yank_len)
             .expect("writes to registers must not be empty");
         self.lengths.push_back(yank_len);
+        self.shapes.push_back(shape);
     }
 
-    fn push(&mut self, value: String) {
+    fn push(&mut self, value: String, shape: RegisterShape) {
         self.values.push_back(value);
         if let Some(last_length) = self.lengths.back_mut() {
             *last_length = NonZeroUsize::new(last_length.get() + 1).unwrap();
+            let last_shape = self.shapes.back_mut().expect("shapes is parallel to lengths");
+            *last_shape = merge_shapes(*last_shape, shape);
         } else {
             self.lengths.push_back(NonZeroUsize::new(1).unwrap());
+            self.shapes.push_back(shape);
+        }
+    }
+}
+
+/// The on-disk form of a single register, used to persist register contents
+/// (and their yank history) across sessions.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SerializedRegister {
+    values: VecDeque<String>,
+    lengths: VecDeque<NonZeroUsize>,
+    shapes: VecDeque<RegisterShape>,
+}
+
+impl From<&Register> for SerializedRegister {
+    fn from(register: &Register) -> Self {
+        Self {
+            values: register.values.clone(),
+            lengths: register.lengths.clone(),
+            shapes: register.shapes.clone(),
+        }
+    }
+}
+
+impl From<SerializedRegister> for Register {
+    fn from(mut serialized: SerializedRegister) -> Self {
+        // `values_nth` relies on `shapes.len() == lengths.len()` and on
+        // `lengths` summing to `values.len()`, an invariant `write`/`push`
+        // always uphold but a truncated or hand-edited state file might not.
+        // Rather than risk a panic later, discard the register's history if
+        // the blob doesn't check out.
+        let lengths_total: usize = serialized.lengths.iter().map(|len| len.get()).sum();
+        if serialized.shapes.len() != serialized.lengths.len()
+            || lengths_total != serialized.values.len()
+        {
+            return Register::default();
+        }
+
+        // Cap restored history at `MAX_REGISTER_HISTORY_LEN`, discarding the
+        // oldest yanks first, the same way `Register::write` does at runtime.
+        while serialized.lengths.len() > MAX_REGISTER_HISTORY_LEN {
+            let oldest_len = serialized.lengths.pop_front().unwrap();
+            serialized.values.drain(..oldest_len.get());
+            serialized.shapes.pop_front();
+        }
+
+        Register {
+            values: serialized.values,
+            lengths: serialized.lengths,
+            shapes: serialized.shapes,
         }
     }
 }
@@ -91,6 +208,10 @@ impl Register {
 /// * Document path (`%`): filename of the current buffer
 /// * System clipboard (`*`)
 /// * Primary clipboard (`+`)
+/// * Yank history ring (`0`..`9`): the `N`-th most recent yank into the
+///   default register (`"`), where `0` is the latest
+/// * Last inserted text (`^`)
+/// * Last command line (`:`)
 #[derive(Debug)]
 pub struct Registers {
     /// The mapping of register to values.
@@ -98,6 +219,12 @@ pub struct Registers {
     inner: HashMap<char, Register>,
     clipboard_provider: Box<dyn ClipboardProvider>,
     pub last_search_register: char,
+    /// The text inserted by the most recently exited insert mode session,
+    /// surfaced as the read-only `^` register.
+    last_inserted_text: String,
+    /// The most recently run command-line command, surfaced as the
+    /// read-only `:` register.
+    last_command_line: String,
 }
 
 impl Default for Registers {
@@ -106,13 +233,20 @@ impl Default for Registers {
             inner: Default::default(),
             clipboard_provider: get_clipboard_provider(),
             last_search_register: '/',
+            last_inserted_text: String::new(),
+            last_command_line: String::new(),
         }
     }
 }
 
 impl Registers {
     pub fn read<'a>(&'a self, name: char, editor: &'a Editor) -> Option<RegisterValues<'a>> {
+        let name = name.to_ascii_lowercase();
         match name {
+            '0'..='9' => {
+                let n = name.to_digit(10).unwrap() as usize;
+                self.read_nth(DEFAULT_REGISTER_NAME, n, editor)
+            }
             '_' => Some(RegisterValues::new(iter::empty())),
             '#' => {
                 let (view, doc) = current_ref!(editor);
@@ -139,6 +273,12 @@ impl Registers {
 
                 Some(RegisterValues::new(iter::once(path)))
             }
+            '^' => Some(RegisterValues::new(iter::once(Cow::Owned(
+                self.last_inserted_text.clone(),
+            )))),
+            ':' => Some(RegisterValues::new(iter::once(Cow::Owned(
+                self.last_command_line.clone(),
+            )))),
             '*' | '+' => Some(read_from_clipboard(
                 self.clipboard_provider.as_ref(),
                 self.inner.get(&name),
@@ -152,12 +292,54 @@ impl Registers {
         }
     }
 
-    pub fn write<I: IntoIterator<Item = String>>(&mut self, name: char, values: I) -> Result<()> {
+    /// Reads the `n`-th most recent yank into register `name`, where `0` is
+    /// the latest yank (equivalent to `read`). This is how numbered
+    /// registers (`"0`..`"9`) surface the yank history ring.
+    pub fn read_nth<'a>(
+        &'a self,
+        name: char,
+        n: usize,
+        editor: &'a Editor,
+    ) -> Option<RegisterValues<'a>> {
+        let name = name.to_ascii_lowercase();
+        if n == 0 {
+            return self.read(name, editor);
+        }
+
+        match name {
+            '_' | '#' | '.' | '%' | '^' | ':' | '*' | '+' | '0'..='9' => None,
+            _ => self
+                .inner
+                .get(&name)
+                .and_then(|register| register.values_nth(n)),
+        }
+    }
+
+    /// Writes `values` to register `name`.
+    ///
+    /// Writing to an uppercase register char (`A`..`Z`) appends the values
+    /// to the lowercase register's most recent yank instead of starting a
+    /// fresh one, matching the Vim convention for building up a register's
+    /// contents across multiple yanks.
+    pub fn write<I: IntoIterator<Item = String>>(
+        &mut self,
+        name: char,
+        values: I,
+        shape: RegisterShape,
+    ) -> Result<()> {
+        let append = name.is_ascii_uppercase();
+        let name = name.to_ascii_lowercase();
         match name {
             '_' => Ok(()),
-            '#' | '.' | '%' => Err(anyhow::anyhow!("Register {name} does not support writing")),
+            '#' | '.' | '%' | '^' | ':' | '0'..='9' => {
+                Err(anyhow::anyhow!("Register {name} does not support writing"))
+            }
             '*' | '+' => {
-                self.inner.entry(name).or_default().write(values);
+                // `'*'`/`'+'` are never ascii-uppercase, so `append` is
+                // always false here; the clipboard registers don't support
+                // uppercase-append.
+                let register = self.inner.entry(name).or_default();
+                register.write(values, shape);
                 let mut contents = String::new();
                 for val in self.inner[&name].values() {
                     if !contents.is_empty() {
@@ -176,16 +358,26 @@ impl Registers {
                 Ok(())
             }
             _ => {
-                self.inner.entry(name).or_default().write(values);
+                let register = self.inner.entry(name).or_default();
+                if append {
+                    for value in values {
+                        register.push(value, shape);
+                    }
+                } else {
+                    register.write(values, shape);
+                }
                 Ok(())
             }
         }
     }
 
-    pub fn push(&mut self, name: char, mut value: String) -> Result<()> {
+    pub fn push(&mut self, name: char, mut value: String, shape: RegisterShape) -> Result<()> {
+        let name = name.to_ascii_lowercase();
         match name {
             '_' => Ok(()),
-            '#' | '.' | '%' => Err(anyhow::anyhow!("Register {name} does not support pushing")),
+            '#' | '.' | '%' | '^' | ':' | '0'..='9' => {
+                Err(anyhow::anyhow!("Register {name} does not support pushing"))
+            }
             '*' | '+' => {
                 let clipboard_type = match name {
                     '+' => ClipboardType::Clipboard,
@@ -199,7 +391,7 @@ impl Registers {
                     anyhow::bail!("Failed to push to register {name}: clipboard does not match register contents");
                 }
 
-                register.push(value.clone());
+                register.push(value.clone(), shape);
                 if !contents.is_empty() {
                     value.push_str(NATIVE_LINE_ENDING.as_str());
                 }
@@ -210,7 +402,7 @@ impl Registers {
                 Ok(())
             }
             _ => {
-                self.inner.entry(name).or_default().push(value);
+                self.inner.entry(name).or_default().push(value, shape);
                 Ok(())
             }
         }
@@ -244,12 +436,26 @@ impl Registers {
                     ('%', "<document path>"),
                     ('+', "<system clipboard>"),
                     ('*', "<primary clipboard>"),
+                    ('^', "<last inserted text>"),
+                    (':', "<last command>"),
                 ]
                 .iter()
                 .copied(),
             )
     }
 
+    /// Records `text` as the contents of the last-inserted-text register
+    /// (`^`). Called by the editor whenever insert mode ends.
+    pub fn set_last_inserted_text(&mut self, text: String) {
+        self.last_inserted_text = text;
+    }
+
+    /// Records `command` as the contents of the last-command register
+    /// (`:`). Called by the editor whenever a command-line command runs.
+    pub fn set_last_command_line(&mut self, command: String) {
+        self.last_command_line = command;
+    }
+
     pub fn clear(&mut self) {
         self.clear_clipboard(ClipboardType::Clipboard);
         self.clear_clipboard(ClipboardType::Selection);
@@ -257,6 +463,7 @@ impl Registers {
     }
 
     pub fn remove(&mut self, name: char) -> bool {
+        let name = name.to_ascii_lowercase();
         match name {
             '*' | '+' => {
                 self.clear_clipboard(match name {
@@ -268,7 +475,7 @@ impl Registers {
 
                 true
             }
-            '_' | '#' | '.' | '%' => false,
+            '_' | '#' | '.' | '%' | '^' | ':' | '0'..='9' => false,
             _ => self.inner.remove(&name).is_some(),
         }
     }
@@ -291,6 +498,32 @@ impl Registers {
     pub fn clipboard_provider_name(&self) -> Cow<str> {
         self.clipboard_provider.name()
     }
+
+    /// Serializes all named registers (and their yank history) to a compact
+    /// on-disk form, skipping the clipboard registers (`*`/`+`) since those
+    /// are backed by the system clipboard rather than editor state.
+    pub fn serialize(&self) -> HashMap<char, SerializedRegister> {
+        self.inner
+            .iter()
+            .filter(|(name, _)| !matches!(name, '*' | '+'))
+            .map(|(name, register)| (*name, SerializedRegister::from(register)))
+            .collect()
+    }
+
+    /// Restores named registers from a form previously produced by
+    /// `serialize`, replacing any existing registers with the same name.
+    ///
+    /// Skips `'*'`/`'+'` entries, mirroring `serialize`: those registers must
+    /// always reflect the live system clipboard, so a stale or hand-edited
+    /// blob can't be used to desync them from it.
+    pub fn load(&mut self, serialized: HashMap<char, SerializedRegister>) {
+        for (name, serialized) in serialized {
+            if matches!(name, '*' | '+') {
+                continue;
+            }
+            self.inner.insert(name, serialized.into());
+        }
+    }
 }
 
 fn read_from_clipboard<'a>(
@@ -355,6 +588,7 @@ fn contents_are_saved(mut values: RegisterValues<'_>, mut contents: &str) -> boo
 // values.
 pub struct RegisterValues<'a> {
     iter: Box<dyn DoubleEndedExactSizeIterator<Item = Cow<'a, str>> + 'a>,
+    shape: RegisterShape,
 }
 
 impl<'a> RegisterValues<'a> {
@@ -362,11 +596,27 @@ impl<'a> RegisterValues<'a> {
         iter: impl DoubleEndedIterator<Item = Cow<'a, str>>
             + ExactSizeIterator<Item = Cow<'a, str>>
             + 'a,
+    ) -> Self {
+        Self::with_shape(iter, RegisterShape::default())
+    }
+
+    fn with_shape(
+        iter: impl DoubleEndedIterator<Item = Cow<'a, str>>
+            + ExactSizeIterator<Item = Cow<'a, str>>
+            + 'a,
+        shape: RegisterShape,
     ) -> Self {
         Self {
             iter: Box::new(iter),
+            shape,
         }
     }
+
+    /// The shape of the selection that produced these values, used by paste
+    /// commands to decide how to insert them relative to the cursor.
+    pub fn shape(&self) -> RegisterShape {
+        self.shape
+    }
 }
 
 impl<'a> Iterator for RegisterValues<'a> {
@@ -402,3 +652,174 @@ impl<'a> ExactSizeIterator for RegisterValues<'a> {
 trait DoubleEndedExactSizeIterator: DoubleEndedIterator + ExactSizeIterator {}
 
 impl<I: DoubleEndedIterator + ExactSizeIterator> DoubleEndedExactSizeIterator for I {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn register(yanks: &[(&[&str], RegisterShape)]) -> Register {
+        let mut register = Register::default();
+        for (values, shape) in yanks {
+            register.write(values.iter().map(|s| s.to_string()), *shape);
+        }
+        register
+    }
+
+    #[test]
+    fn values_nth_returns_each_yank_most_recent_first() {
+        let register = register(&[
+            (&["a", "b"], RegisterShape::Charwise),
+            (&["c"], RegisterShape::Linewise),
+            (&["d", "e", "f"], RegisterShape::Blockwise),
+        ]);
+
+        let nth = |n: usize| {
+            register
+                .values_nth(n)
+                .map(|values| values.collect::<Vec<_>>())
+        };
+
+        assert_eq!(
+            nth(0),
+            Some(vec![
+                Cow::Borrowed("d"),
+                Cow::Borrowed("e"),
+                Cow::Borrowed("f")
+            ])
+        );
+        assert_eq!(
+            register.values_nth(0).unwrap().shape(),
+            RegisterShape::Blockwise
+        );
+
+        assert_eq!(nth(1), Some(vec![Cow::Borrowed("c")]));
+        assert_eq!(
+            register.values_nth(1).unwrap().shape(),
+            RegisterShape::Linewise
+        );
+
+        assert_eq!(nth(2), Some(vec![Cow::Borrowed("a"), Cow::Borrowed("b")]));
+        assert_eq!(
+            register.values_nth(2).unwrap().shape(),
+            RegisterShape::Charwise
+        );
+
+        assert!(register.values_nth(3).is_none());
+    }
+
+    #[test]
+    fn write_uppercase_appends_onto_lowercase_register_and_merges_shape() {
+        let mut registers = Registers::default();
+        registers
+            .write('a', ["line one".to_string()], RegisterShape::Linewise)
+            .unwrap();
+        registers
+            .write('A', ["more".to_string()], RegisterShape::Charwise)
+            .unwrap();
+
+        let register = &registers.inner[&'a'];
+        assert_eq!(register.lengths.len(), 1, "append must not start a new yank");
+        assert_eq!(
+            register.values.iter().map(String::as_str).collect::<Vec<_>>(),
+            vec!["line one", "more"]
+        );
+        // Vim's rule: if either side of an append is linewise, the combined
+        // yank is linewise.
+        assert_eq!(
+            register.shapes.back().copied(),
+            Some(RegisterShape::Linewise)
+        );
+    }
+
+    #[test]
+    fn last_inserted_and_last_command_registers_reject_writes() {
+        let mut registers = Registers::default();
+        registers.set_last_inserted_text("inserted text".to_string());
+        registers.set_last_command_line(":x".to_string());
+
+        assert!(registers
+            .write('^', ["x".to_string()], RegisterShape::Charwise)
+            .is_err());
+        assert!(registers
+            .push('^', "x".to_string(), RegisterShape::Charwise)
+            .is_err());
+        assert!(registers
+            .write(':', ["x".to_string()], RegisterShape::Charwise)
+            .is_err());
+        assert!(registers
+            .push(':', "x".to_string(), RegisterShape::Charwise)
+            .is_err());
+    }
+
+    #[test]
+    fn serialize_load_round_trips_and_caps_history() {
+        let mut registers = Registers::default();
+        // One write beyond `MAX_REGISTER_HISTORY_LEN` leaves exactly
+        // `MAX_REGISTER_HISTORY_LEN + 1` yanks in the live register, since
+        // `Register::write` only evicts the oldest yank once the history is
+        // already over the cap.
+        for i in 0..=MAX_REGISTER_HISTORY_LEN {
+            let shape = if i % 2 == 0 {
+                RegisterShape::Charwise
+            } else {
+                RegisterShape::Linewise
+            };
+            registers.write('a', [i.to_string()], shape).unwrap();
+        }
+        assert_eq!(
+            registers.inner[&'a'].lengths.len(),
+            MAX_REGISTER_HISTORY_LEN + 1
+        );
+
+        let serialized = registers.serialize();
+        assert_eq!(serialized[&'a'].lengths.len(), MAX_REGISTER_HISTORY_LEN + 1);
+
+        let mut loaded = Registers::default();
+        loaded.load(serialized);
+
+        let register = &loaded.inner[&'a'];
+        assert_eq!(register.lengths.len(), MAX_REGISTER_HISTORY_LEN);
+        // The oldest yank ("0") should have been dropped to make room.
+        assert_eq!(register.values.front().map(String::as_str), Some("1"));
+        assert_eq!(
+            register.values.back().map(String::as_str),
+            Some(MAX_REGISTER_HISTORY_LEN.to_string().as_str())
+        );
+        // Yank "0" (Charwise) was dropped to make room, so the oldest
+        // surviving yank is "1" (Linewise); shapes must still alternate from
+        // there, proving `shapes` round-trips through `serialize`/`load`
+        // rather than just `values`/`lengths`.
+        let expected_shapes: VecDeque<_> = (1..=MAX_REGISTER_HISTORY_LEN)
+            .map(|i| {
+                if i % 2 == 0 {
+                    RegisterShape::Charwise
+                } else {
+                    RegisterShape::Linewise
+                }
+            })
+            .collect();
+        assert_eq!(register.shapes, expected_shapes);
+    }
+
+    #[test]
+    fn load_discards_register_with_inconsistent_serialized_lengths() {
+        // `lengths` claims two yanks of sizes 1 and 2 (summing to 3), but
+        // `values` only holds 2 entries and `shapes` only has 1 tag. Either
+        // mismatch would make `values_nth` panic if used as-is, so
+        // `Registers::load` must discard the whole register instead.
+        let corrupt = SerializedRegister {
+            values: VecDeque::from(["a".to_string(), "b".to_string()]),
+            lengths: VecDeque::from([NonZeroUsize::new(1).unwrap(), NonZeroUsize::new(2).unwrap()]),
+            shapes: VecDeque::from([RegisterShape::Charwise]),
+        };
+
+        let mut registers = Registers::default();
+        registers.load(HashMap::from([('a', corrupt)]));
+
+        let register = &registers.inner[&'a'];
+        assert!(register.values.is_empty());
+        assert!(register.lengths.is_empty());
+        assert!(register.shapes.is_empty());
+        assert!(register.values_nth(0).is_none());
+    }
+}